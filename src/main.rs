@@ -1,16 +1,796 @@
 use structopt::StructOpt;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 use log::{info, LevelFilter, warn};
 use simplelog::{Config as LogConfig, TermLogger, TerminalMode};
-use hyper::{Body, Request, Response, Server};
-use hyper::service::{make_service_fn, service_fn};
+use bytes::BytesMut;
+use hyper::{Body, HeaderMap, Request, Response, Server};
+use hyper::header::{HeaderValue, CONNECTION};
+use hyper::server::accept::Accept;
+use hyper::service::{make_service_fn, service_fn, Service};
 use hyper::body::to_bytes;
-use hyper::client::Client;
-use tokio::time::{timeout, Duration};
-use futures::future::select_all;
+use hyper::client::{Client, HttpConnector};
+use hyperlocal::{UnixClientExt, UnixServerExt};
+use proxy_protocol::{version1, version2, ProxyHeader};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::{TcpListener, TcpStream, UnixStream};
+use tokio::process::{Child, Command as TokioCommand};
+use tokio::sync::RwLock;
+use tokio::time::{sleep, timeout, Duration};
+use futures::future::{join_all, select_all};
 use uuid::Uuid;
 
+/// How many times to retry connecting to a freshly spawned backend before
+/// giving up and proxying to it anyway.
+const SPAWN_READY_RETRIES: u32 = 30;
+/// Delay between readiness retries for a freshly spawned backend.
+const SPAWN_READY_RETRY_DELAY: Duration = Duration::from_millis(500);
+/// How often to poll the configuration file for changes.
+const CONFIG_RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Headers that are specific to a single transport hop and must never be
+/// forwarded verbatim by a reverse proxy.
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailers",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Remove the standard hop-by-hop headers, plus any header names listed in
+/// the `Connection` header itself, from `headers` in place.
+fn strip_hop_by_hop_headers(headers: &mut HeaderMap) {
+    let extra: Vec<String> = headers.get(CONNECTION)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(',').map(|name| name.trim().to_lowercase()).filter(|name| !name.is_empty()).collect())
+        .unwrap_or_default();
+
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(*name);
+    }
+    for name in extra {
+        headers.remove(name.as_str());
+    }
+}
+
+/// Resolve `request_path` against `root` and stream the file back with a
+/// `Content-Type` guessed from its extension, falling back to that
+/// directory's `index.html` if the resolved path is a directory. Returns
+/// 403 if the resolved path escapes `root` and 404 if it doesn't exist.
+fn serve_static_file(root: &str, request_path: &str) -> Response<Body> {
+    let candidate = std::path::Path::new(root).join(request_path.trim_start_matches('/'));
+
+    let canonical_root = match fs::canonicalize(root) {
+        Ok(path) => path,
+        Err(_) => return not_found_response(),
+    };
+    let canonical_candidate = match fs::canonicalize(&candidate) {
+        Ok(path) => path,
+        Err(_) => return not_found_response(),
+    };
+    if !canonical_candidate.starts_with(&canonical_root) {
+        warn!("Refusing to serve {} outside of serve_dir {}", canonical_candidate.display(), root);
+        return Response::builder()
+            .status(403)
+            .body(Body::from("Forbidden"))
+            .expect("Failed to build response");
+    }
+
+    // A request for a directory (most commonly `/`) serves that directory's
+    // index.html, if any, rather than 404ing.
+    let canonical_candidate = if canonical_candidate.is_dir() {
+        canonical_candidate.join("index.html")
+    } else {
+        canonical_candidate
+    };
+
+    match fs::read(&canonical_candidate) {
+        Ok(contents) => {
+            let content_type = mime_guess::from_path(&canonical_candidate).first_or_octet_stream();
+            Response::builder()
+                .status(200)
+                .header("content-type", content_type.as_ref())
+                .body(Body::from(contents))
+                .expect("Failed to build response")
+        }
+        Err(_) => not_found_response(),
+    }
+}
+
+#[cfg(test)]
+mod serve_static_file_tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("dev-server-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn serves_file_under_root() {
+        let root = temp_root("file");
+        fs::write(root.join("hello.txt"), b"hi").unwrap();
+
+        let resp = serve_static_file(root.to_str().unwrap(), "/hello.txt");
+
+        assert_eq!(resp.status(), 200);
+    }
+
+    #[test]
+    fn falls_back_to_index_html_for_directories() {
+        let root = temp_root("index");
+        fs::write(root.join("index.html"), b"<html></html>").unwrap();
+
+        let resp = serve_static_file(root.to_str().unwrap(), "/");
+
+        assert_eq!(resp.status(), 200);
+    }
+
+    #[test]
+    fn missing_index_html_is_not_found() {
+        let root = temp_root("no-index");
+
+        let resp = serve_static_file(root.to_str().unwrap(), "/");
+
+        assert_eq!(resp.status(), 404);
+    }
+
+    #[test]
+    fn refuses_path_traversal_outside_root() {
+        let root = temp_root("traversal");
+        fs::create_dir_all(root.join("public")).unwrap();
+        fs::write(root.join("secret.txt"), b"top secret").unwrap();
+
+        let resp = serve_static_file(root.join("public").to_str().unwrap(), "/../secret.txt");
+
+        assert_eq!(resp.status(), 403);
+    }
+
+    #[test]
+    fn missing_file_is_not_found() {
+        let root = temp_root("missing");
+
+        let resp = serve_static_file(root.to_str().unwrap(), "/nope.txt");
+
+        assert_eq!(resp.status(), 404);
+    }
+}
+
+fn not_found_response() -> Response<Body> {
+    Response::builder()
+        .status(404)
+        .body(Body::from("Not Found"))
+        .expect("Failed to build response")
+}
+
+/// Append the client's IP to the `X-Forwarded-For` chain and set
+/// `X-Forwarded-Proto`/`X-Forwarded-Host` on a request about to be sent
+/// upstream.
+fn apply_forwarded_headers(headers: &mut HeaderMap, client_ip: std::net::IpAddr, host: Option<&str>) {
+    let forwarded_for = match headers.get("x-forwarded-for").and_then(|value| value.to_str().ok()) {
+        Some(existing) if !existing.is_empty() => format!("{}, {}", existing, client_ip),
+        _ => client_ip.to_string(),
+    };
+    if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+        headers.insert("x-forwarded-for", value);
+    }
+    headers.insert("x-forwarded-proto", HeaderValue::from_static("http"));
+    if let Some(host) = host {
+        if let Ok(value) = HeaderValue::from_str(host) {
+            headers.insert("x-forwarded-host", value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod forwarded_header_tests {
+    use super::*;
+
+    #[test]
+    fn strips_standard_hop_by_hop_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONNECTION, HeaderValue::from_static("keep-alive"));
+        headers.insert("keep-alive", HeaderValue::from_static("timeout=5"));
+        headers.insert("transfer-encoding", HeaderValue::from_static("chunked"));
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+
+        strip_hop_by_hop_headers(&mut headers);
+
+        assert!(headers.get(CONNECTION).is_none());
+        assert!(headers.get("keep-alive").is_none());
+        assert!(headers.get("transfer-encoding").is_none());
+        assert_eq!(headers.get("content-type").unwrap(), "application/json");
+    }
+
+    #[test]
+    fn strips_extra_headers_named_in_the_connection_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONNECTION, HeaderValue::from_static("X-Custom"));
+        headers.insert("x-custom", HeaderValue::from_static("secret"));
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+
+        strip_hop_by_hop_headers(&mut headers);
+
+        assert!(headers.get("x-custom").is_none());
+        assert_eq!(headers.get("content-type").unwrap(), "application/json");
+    }
+
+    #[test]
+    fn appends_to_an_existing_x_forwarded_for_chain() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("203.0.113.1"));
+
+        apply_forwarded_headers(&mut headers, "192.168.0.5".parse().unwrap(), None);
+
+        assert_eq!(headers.get("x-forwarded-for").unwrap(), "203.0.113.1, 192.168.0.5");
+    }
+
+    #[test]
+    fn starts_x_forwarded_for_when_absent() {
+        let mut headers = HeaderMap::new();
+
+        apply_forwarded_headers(&mut headers, "192.168.0.5".parse().unwrap(), None);
+
+        assert_eq!(headers.get("x-forwarded-for").unwrap(), "192.168.0.5");
+    }
+
+    #[test]
+    fn sets_x_forwarded_host_only_when_a_host_is_present() {
+        let mut headers = HeaderMap::new();
+
+        apply_forwarded_headers(&mut headers, "192.168.0.5".parse().unwrap(), None);
+        assert!(headers.get("x-forwarded-host").is_none());
+
+        apply_forwarded_headers(&mut headers, "192.168.0.5".parse().unwrap(), Some("api.local.gd"));
+        assert_eq!(headers.get("x-forwarded-host").unwrap(), "api.local.gd");
+    }
+}
+
+/// Maximum number of bytes to buffer while looking for a leading PROXY
+/// protocol header on an inbound connection before giving up and treating
+/// the connection as unproxied plain HTTP.
+const PROXY_HEADER_MAX_BYTES: usize = 256;
+
+/// The original client address carried by a parsed PROXY header, if the
+/// header actually identifies one (both versions support a `LOCAL`/
+/// `UNSPEC` variant that carries no addresses, e.g. health checks).
+fn proxy_header_source_addr(header: &ProxyHeader) -> Option<SocketAddr> {
+    match header {
+        ProxyHeader::Version1 { addresses: version1::ProxyAddresses::Ipv4 { source, .. } } => Some(SocketAddr::V4(*source)),
+        ProxyHeader::Version1 { addresses: version1::ProxyAddresses::Ipv6 { source, .. } } => Some(SocketAddr::V6(*source)),
+        ProxyHeader::Version1 { .. } => None,
+        ProxyHeader::Version2 { addresses: version2::ProxyAddresses::Ipv4 { source, .. }, .. } => Some(SocketAddr::V4(*source)),
+        ProxyHeader::Version2 { addresses: version2::ProxyAddresses::Ipv6 { source, .. }, .. } => Some(SocketAddr::V6(*source)),
+        ProxyHeader::Version2 { .. } => None,
+        _ => None,
+    }
+}
+
+/// What a [`ProxyProtocolStream`] is currently doing with bytes read off the
+/// underlying socket.
+enum ProxyProtocolState {
+    /// Still accumulating bytes, trying to parse a PROXY header out of them.
+    Searching(BytesMut),
+    /// A header decision has been made; these bytes are real request data
+    /// waiting to be handed to the caller.
+    Buffered(BytesMut),
+    /// The header (if any) has been fully delivered; read straight from the
+    /// underlying socket from now on.
+    Passthrough,
+}
+
+/// A freshly accepted TCP connection that may start with a PROXY protocol
+/// v1/v2 header. The header, if present, is parsed and stripped before any
+/// bytes reach hyper's HTTP parser, and the client address it carries is
+/// published through `effective_addr` for use in `X-Forwarded-For`.
+struct ProxyProtocolStream {
+    inner: TcpStream,
+    effective_addr: Arc<Mutex<SocketAddr>>,
+    state: ProxyProtocolState,
+}
+
+impl ProxyProtocolStream {
+    fn new(inner: TcpStream, peer_addr: SocketAddr) -> Self {
+        ProxyProtocolStream {
+            inner,
+            effective_addr: Arc::new(Mutex::new(peer_addr)),
+            state: ProxyProtocolState::Searching(BytesMut::new()),
+        }
+    }
+
+    /// A handle to the client address this connection will resolve to, once
+    /// its (possible) PROXY header has been read. Safe to read after the
+    /// first byte of the request has been parsed by hyper, since the header
+    /// always precedes it on the wire.
+    fn effective_addr(&self) -> Arc<Mutex<SocketAddr>> {
+        self.effective_addr.clone()
+    }
+}
+
+impl AsyncRead for ProxyProtocolStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, out: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            match &mut this.state {
+                ProxyProtocolState::Passthrough => return Pin::new(&mut this.inner).poll_read(cx, out),
+                ProxyProtocolState::Buffered(data) => {
+                    if data.is_empty() {
+                        this.state = ProxyProtocolState::Passthrough;
+                        continue;
+                    }
+                    let n = std::cmp::min(out.remaining(), data.len());
+                    out.put_slice(&data.split_to(n));
+                    if data.is_empty() {
+                        this.state = ProxyProtocolState::Passthrough;
+                    }
+                    return Poll::Ready(Ok(()));
+                }
+                ProxyProtocolState::Searching(buf) => {
+                    let mut chunk = [0u8; 256];
+                    let mut read_buf = ReadBuf::new(&mut chunk);
+                    match Pin::new(&mut this.inner).poll_read(cx, &mut read_buf) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                        Poll::Ready(Ok(())) => {
+                            let filled = read_buf.filled();
+                            if filled.is_empty() {
+                                let data = std::mem::take(buf);
+                                this.state = ProxyProtocolState::Buffered(data);
+                                continue;
+                            }
+                            buf.extend_from_slice(filled);
+
+                            // `proxy_protocol::parse` advances its buffer argument as it
+                            // scans even when it returns `Err` (e.g. a header split across
+                            // reads looks like `Err(UnexpectedEof)` but still consumes the
+                            // recognizable prefix). Parse a throwaway clone so a failed
+                            // attempt doesn't corrupt the bytes we still need to re-parse
+                            // once more data arrives.
+                            let mut probe = buf.clone();
+                            match proxy_protocol::parse(&mut probe) {
+                                Ok(header) => {
+                                    if let Some(addr) = proxy_header_source_addr(&header) {
+                                        *this.effective_addr.lock().unwrap() = addr;
+                                    }
+                                    this.state = ProxyProtocolState::Buffered(probe);
+                                }
+                                Err(_) if buf.len() < PROXY_HEADER_MAX_BYTES => {}
+                                Err(_) => {
+                                    let data = std::mem::take(buf);
+                                    this.state = ProxyProtocolState::Buffered(data);
+                                }
+                            }
+                            continue;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl AsyncWrite for ProxyProtocolStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod proxy_protocol_stream_tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    /// Accepts one connection on an ephemeral loopback port and returns both
+    /// ends of it as `(client, server)`; the server-side `TcpStream` is the
+    /// one to wrap in a `ProxyProtocolStream`.
+    async fn accepted_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = TcpStream::connect(addr).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (client, server)
+    }
+
+    #[tokio::test]
+    async fn reads_request_bytes_sent_after_the_header_in_a_separate_write() {
+        let (mut client, server) = accepted_pair().await;
+        let peer_addr: SocketAddr = "203.0.113.1:1234".parse().unwrap();
+        let mut stream = ProxyProtocolStream::new(server, peer_addr);
+
+        client.write_all(b"PROXY TCP4 192.168.0.1 192.168.0.2 5555 443\r\n").await.unwrap();
+        client.flush().await.unwrap();
+
+        // Start reading before the request itself is written, so the header
+        // and the request land in separate socket reads -- the scenario
+        // that used to trip a spurious EOF once the header-only read left
+        // `Buffered` holding zero bytes.
+        let read_task = tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            let n = stream.read(&mut buf).await.unwrap();
+            (stream, buf, n)
+        });
+
+        sleep(Duration::from_millis(50)).await;
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        client.flush().await.unwrap();
+
+        let (stream, buf, n) = timeout(Duration::from_secs(1), read_task).await
+            .expect("poll_read must not report EOF when only the header had arrived so far")
+            .unwrap();
+
+        assert!(n > 0);
+        assert_eq!(&buf[..n], b"GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(
+            *stream.effective_addr().lock().unwrap(),
+            "192.168.0.1:5555".parse::<SocketAddr>().unwrap(),
+        );
+    }
+
+    #[tokio::test]
+    async fn gives_up_and_passes_through_once_non_proxy_traffic_fills_the_search_buffer() {
+        let (mut client, server) = accepted_pair().await;
+        let peer_addr: SocketAddr = "203.0.113.1:1234".parse().unwrap();
+        let mut stream = ProxyProtocolStream::new(server, peer_addr);
+
+        let junk = vec![b'x'; PROXY_HEADER_MAX_BYTES + 16];
+        client.write_all(&junk).await.unwrap();
+        client.flush().await.unwrap();
+
+        let mut buf = [0u8; 64];
+        let n = timeout(Duration::from_secs(1), stream.read(&mut buf)).await
+            .expect("poll_read must give up looking for a PROXY header once PROXY_HEADER_MAX_BYTES is exceeded")
+            .unwrap();
+
+        assert!(n > 0);
+        assert!(buf[..n].iter().all(|&b| b == b'x'));
+        assert_eq!(*stream.effective_addr().lock().unwrap(), peer_addr);
+    }
+
+    #[test]
+    fn proxy_header_source_addr_extracts_v1_ipv4_source() {
+        let header = ProxyHeader::Version1 {
+            addresses: version1::ProxyAddresses::Ipv4 {
+                source: "192.168.0.1:5555".parse().unwrap(),
+                destination: "192.168.0.2:443".parse().unwrap(),
+            },
+        };
+
+        assert_eq!(proxy_header_source_addr(&header), Some("192.168.0.1:5555".parse().unwrap()));
+    }
+
+    #[test]
+    fn proxy_header_source_addr_is_none_for_local_connections() {
+        let header = ProxyHeader::Version1 { addresses: version1::ProxyAddresses::Unknown };
+
+        assert_eq!(proxy_header_source_addr(&header), None);
+    }
+}
+
+/// A TCP listener that wraps every accepted connection in
+/// [`ProxyProtocolStream`], so a leading PROXY header is parsed and
+/// stripped before hyper ever sees the connection. Used in place of hyper's
+/// own `AddrIncoming` when `proxy_protocol_in` is enabled; in exchange we
+/// lose `AddrIncoming`'s TCP keepalive/nodelay tuning, which this dev tool
+/// doesn't otherwise rely on.
+struct ProxyProtocolIncoming {
+    listener: TcpListener,
+}
+
+impl Accept for ProxyProtocolIncoming {
+    type Conn = ProxyProtocolStream;
+    type Error = io::Error;
+
+    fn poll_accept(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        match self.get_mut().listener.poll_accept(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(e)) => Poll::Ready(Some(Err(e))),
+            Poll::Ready(Ok((stream, peer_addr))) => Poll::Ready(Some(Ok(ProxyProtocolStream::new(stream, peer_addr)))),
+        }
+    }
+}
+
+/// Wraps an [`HttpConnector`] to prepend a PROXY protocol v1 header
+/// carrying the real downstream client address to every upstream
+/// connection it opens. Used when `proxy_protocol_out` is enabled, since
+/// from the backend's point of view `dev-server` itself is now the TCP
+/// peer and the original client IP would otherwise be lost.
+///
+/// Only v1 is emitted; there's no flag to choose v2 output, even though
+/// inbound parsing (`ProxyProtocolStream`) already understands both
+/// versions. Emitting v2 here would be a small, self-contained follow-up
+/// if a backend ever needs it.
+#[derive(Clone)]
+struct ProxyProtocolConnector {
+    inner: HttpConnector,
+    client_addr: SocketAddr,
+}
+
+impl ProxyProtocolConnector {
+    fn new(client_addr: SocketAddr) -> Self {
+        ProxyProtocolConnector {
+            inner: HttpConnector::new(),
+            client_addr,
+        }
+    }
+}
+
+impl Service<hyper::Uri> for ProxyProtocolConnector {
+    type Response = TcpStream;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = Pin<Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, dst: hyper::Uri) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let client_addr = self.client_addr;
+        Box::pin(async move {
+            let mut stream = inner.call(dst).await?;
+            let destination_addr = stream.local_addr()?;
+
+            let addresses = match (client_addr, destination_addr) {
+                (SocketAddr::V4(source), SocketAddr::V4(destination)) => version1::ProxyAddresses::Ipv4 { source, destination },
+                (SocketAddr::V6(source), SocketAddr::V6(destination)) => version1::ProxyAddresses::Ipv6 { source, destination },
+                _ => version1::ProxyAddresses::Unknown,
+            };
+            let encoded = proxy_protocol::encode(ProxyHeader::Version1 { addresses })?;
+            stream.write_all(&encoded).await?;
+
+            Ok(stream)
+        })
+    }
+}
+
+/// A single captured request/response transaction, written as one line of
+/// newline-delimited JSON when `capture` is configured and read back line by
+/// line by `--replay`. `response_body` holds the raw body text when it's
+/// valid UTF-8 (the common case for a dev proxy's JSON/HTML backends) and a
+/// base64 encoding of it otherwise, with `response_body_base64` recording
+/// which one, so a binary backend (images, protobuf, gzip, ...) is captured
+/// intact instead of panicking the connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CaptureRecord {
+    request_id: String,
+    timestamp_ms: u128,
+    method: String,
+    uri: String,
+    request_headers: HashMap<String, String>,
+    request_body: String,
+    status: u16,
+    response_headers: HashMap<String, String>,
+    response_body: String,
+    #[serde(default)]
+    response_body_base64: bool,
+    latency_ms: u128,
+}
+
+/// Render `body_bytes` for a [`CaptureRecord`]: as UTF-8 text when possible,
+/// or base64 with `response_body_base64` set when the upstream response
+/// isn't text (images, protobuf, gzip, ...).
+fn encode_capture_body(body_bytes: &[u8]) -> (String, bool) {
+    match std::str::from_utf8(body_bytes) {
+        Ok(text) => (text.to_string(), false),
+        Err(_) => (base64::encode(body_bytes), true),
+    }
+}
+
+/// Flatten a `HeaderMap` into a plain string map for JSON capture. Headers
+/// repeated with the same name collapse to their last value, which is fine
+/// for this tool's record-and-diff use case.
+fn headers_to_map(headers: &HeaderMap) -> HashMap<String, String> {
+    headers.iter()
+        .filter_map(|(name, value)| value.to_str().ok().map(|value| (name.as_str().to_string(), value.to_string())))
+        .collect()
+}
+
+/// Append `record` as one line of JSON to `path`, logging rather than
+/// failing the request if the capture file can't be written.
+fn write_capture_record(path: &str, record: &CaptureRecord) {
+    let line = match serde_json::to_string(record) {
+        Ok(line) => line,
+        Err(e) => {
+            warn!("Failed to serialize capture record {}: {}", record.request_id, e);
+            return;
+        }
+    };
+
+    let result = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", line));
+
+    if let Err(e) = result {
+        warn!("Failed to write capture record to {}: {}", path, e);
+    }
+}
+
+/// Read back a `--capture` file and replay each recorded request against
+/// `target`, diffing the freshly observed status/body against what was
+/// recorded.
+async fn run_replay(path: String, target: String) {
+    let raw = fs::read_to_string(&path).expect("Failed to read capture file");
+    let client = Client::new();
+    let target_uri: hyper::Uri = target.parse().expect("Invalid replay target URL");
+
+    let mut total = 0;
+    let mut mismatches = 0;
+
+    for line in raw.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: CaptureRecord = match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(e) => {
+                warn!("Skipping unparseable capture record: {}", e);
+                continue;
+            }
+        };
+        total += 1;
+
+        let path_and_query = record.uri.parse::<hyper::Uri>()
+            .ok()
+            .and_then(|uri| uri.path_and_query().cloned())
+            .map(|pq| pq.to_string())
+            .unwrap_or_else(|| record.uri.clone());
+        let absolute_uri = format!(
+            "{}://{}{}",
+            target_uri.scheme_str().unwrap_or("http"),
+            target_uri.authority().expect("Replay target must include host:port"),
+            path_and_query,
+        );
+
+        let method: hyper::Method = match record.method.parse() {
+            Ok(method) => method,
+            Err(e) => {
+                mismatches += 1;
+                warn!("[{}] skipping replay record with invalid method {:?}: {}", record.request_id, record.method, e);
+                continue;
+            }
+        };
+        let mut new_req = Request::builder()
+            .method(method)
+            .uri(absolute_uri)
+            .body(Body::from(record.request_body.clone()))
+            .expect("Failed to build replay request");
+        for (name, value) in &record.request_headers {
+            if let (Ok(name), Ok(value)) = (hyper::header::HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                new_req.headers_mut().insert(name, value);
+            }
+        }
+
+        match client.request(new_req).await {
+            Ok(mut response) => {
+                let status = response.status().as_u16();
+                let body_bytes = to_bytes(response.body_mut()).await.unwrap_or_default();
+                let (body, _) = encode_capture_body(&body_bytes);
+
+                if status == record.status && body == record.response_body {
+                    info!("[{}] replay matches recorded response ({})", record.request_id, status);
+                } else {
+                    mismatches += 1;
+                    warn!(
+                        "[{}] replay diverged: recorded status {} body {:?}, got status {} body {:?}",
+                        record.request_id, record.status, record.response_body, status, body
+                    );
+                }
+            }
+            Err(e) => {
+                mismatches += 1;
+                warn!("[{}] replay request failed: {}", record.request_id, e);
+            }
+        }
+    }
+
+    info!("Replay finished: {}/{} transactions matched", total - mismatches, total);
+}
+
+#[cfg(test)]
+mod capture_tests {
+    use super::*;
+
+    fn temp_capture_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("dev-server-test-capture-{}-{}.jsonl", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn sample_record(method: &str) -> CaptureRecord {
+        CaptureRecord {
+            request_id: "req-1".to_string(),
+            timestamp_ms: 0,
+            method: method.to_string(),
+            uri: "/".to_string(),
+            request_headers: HashMap::new(),
+            request_body: String::new(),
+            status: 200,
+            response_headers: HashMap::new(),
+            response_body: "ok".to_string(),
+            response_body_base64: false,
+            latency_ms: 0,
+        }
+    }
+
+    #[test]
+    fn encode_capture_body_keeps_utf8_text_as_is() {
+        let (body, is_base64) = encode_capture_body(b"hello world");
+
+        assert_eq!(body, "hello world");
+        assert!(!is_base64);
+    }
+
+    #[test]
+    fn encode_capture_body_base64_encodes_non_utf8_bytes() {
+        let bytes = [0xff, 0xfe, 0xfd];
+
+        let (body, is_base64) = encode_capture_body(&bytes);
+
+        assert!(is_base64);
+        assert_eq!(base64::decode(body).unwrap(), bytes);
+    }
+
+    #[test]
+    fn headers_to_map_collects_printable_header_values() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+
+        let map = headers_to_map(&headers);
+
+        assert_eq!(map.get("content-type"), Some(&"application/json".to_string()));
+    }
+
+    #[test]
+    fn write_capture_record_appends_a_json_line() {
+        let path = temp_capture_path("append");
+        let record = sample_record("GET");
+
+        write_capture_record(path.to_str().unwrap(), &record);
+
+        let raw = fs::read_to_string(&path).unwrap();
+        let read_back: CaptureRecord = serde_json::from_str(raw.trim()).unwrap();
+        assert_eq!(read_back.request_id, "req-1");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn run_replay_skips_a_record_with_an_invalid_method_instead_of_panicking() {
+        let path = temp_capture_path("bad-method");
+        let mut record = sample_record("NOT A METHOD");
+        record.request_id = "bad".to_string();
+        fs::write(&path, format!("{}\n", serde_json::to_string(&record).unwrap())).unwrap();
+
+        // Regression test for the fix commit paired with this request: a
+        // malformed recorded method used to panic the whole replay run via
+        // `.expect(...)` instead of being skipped like other bad records.
+        run_replay(path.to_str().unwrap().to_string(), "http://127.0.0.1:1".to_string()).await;
+
+        let _ = fs::remove_file(&path);
+    }
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(name = "dev-server", about = "A simple development server.")]
 struct Opt {
@@ -29,19 +809,213 @@ struct Opt {
     /// Default response when no proxy destinations are set
     #[structopt(short="dr",long)]
     default_response: Option<String>,
+
+    /// Serve static files out of this directory when no proxy destination matches
+    #[structopt(long)]
+    serve_dir: Option<String>,
+
+    /// Prepend a PROXY protocol v1 header to each forwarded upstream connection
+    #[structopt(long)]
+    proxy_protocol_out: bool,
+
+    /// Parse a PROXY protocol header off inbound connections before treating them as HTTP
+    #[structopt(long)]
+    proxy_protocol_in: bool,
+
+    /// Record every proxied transaction as newline-delimited JSON to this file
+    #[structopt(long)]
+    capture: Option<String>,
+
+    /// Replay transactions previously recorded with --capture against --replay-target
+    #[structopt(long)]
+    replay: Option<String>,
+
+    /// Backend URL to send replayed requests to, required together with --replay
+    #[structopt(long)]
+    replay_target: Option<String>,
+}
+
+/// A backend process to launch on startup before routing traffic to it.
+#[derive(Debug, Clone, Deserialize)]
+struct SpawnConfig {
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    envs: HashMap<String, String>,
+}
+
+/// A proxy target, either a plain URL or a URL paired with a backend process
+/// the server should spawn and supervise itself.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ProxyTarget {
+    Url(String),
+    Spawned {
+        url: String,
+        spawn: Option<SpawnConfig>,
+    },
+}
+
+impl ProxyTarget {
+    fn url(&self) -> &str {
+        match self {
+            ProxyTarget::Url(url) => url,
+            ProxyTarget::Spawned { url, .. } => url,
+        }
+    }
+
+    fn spawn(&self) -> Option<&SpawnConfig> {
+        match self {
+            ProxyTarget::Url(_) => None,
+            ProxyTarget::Spawned { spawn, .. } => spawn.as_ref(),
+        }
+    }
+}
+
+/// Either a flat list of proxy targets applied to every request, or a map of
+/// `Host` header value to the targets that host should be routed to.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ProxyDestinations {
+    Flat(Vec<ProxyTarget>),
+    ByHost(HashMap<String, Vec<ProxyTarget>>),
+}
+
+impl ProxyDestinations {
+    /// Resolve the proxy targets for a request, given its `Host` header value.
+    ///
+    /// The header is matched case-insensitively and with any `:port` suffix
+    /// stripped first, since dev-server is normally bound to a non-default
+    /// port and real `Host` headers carry one even when the configured key
+    /// is a bare hostname.
+    fn targets_for(&self, host: Option<&str>) -> Option<Vec<ProxyTarget>> {
+        match self {
+            ProxyDestinations::Flat(targets) => Some(targets.clone()),
+            ProxyDestinations::ByHost(hosts) => {
+                let host = host?;
+                let host = host.split(':').next().unwrap_or(host).to_ascii_lowercase();
+                hosts.get(&host).cloned()
+            }
+        }
+    }
+
+    /// All proxy targets configured, regardless of routing mode, for startup
+    /// tasks like spawning backend processes.
+    fn all_targets(&self) -> Vec<ProxyTarget> {
+        match self {
+            ProxyDestinations::Flat(targets) => targets.clone(),
+            ProxyDestinations::ByHost(hosts) => hosts.values().flat_map(|v| v.clone()).collect(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct Config {
     listen_address: Option<String>,
-    proxy_destinations: Option<Vec<String>>,
+    proxy_destinations: Option<ProxyDestinations>,
     default_response: Option<String>,
+    serve_dir: Option<String>,
+    #[serde(default)]
+    proxy_protocol_out: bool,
+    #[serde(default)]
+    proxy_protocol_in: bool,
+    capture: Option<String>,
+}
+
+/// Drop empty/blank targets from a freshly parsed `proxy_destinations`,
+/// collapsing a now-empty flat list (or a by-host entry left with no
+/// targets) to `None`/removed. Applied both at startup and whenever the
+/// configuration file is hot-reloaded.
+///
+/// By-host keys are also lowercased here (merging any targets that collide
+/// once lowercased), so `targets_for`'s case-insensitive `Host` header match
+/// actually has a lowercase key to look up on both sides.
+fn normalize_proxy_destinations(proxy_destinations: Option<ProxyDestinations>) -> Option<ProxyDestinations> {
+    match proxy_destinations {
+        Some(ProxyDestinations::Flat(mut targets)) => {
+            targets.retain(|proxy| !proxy.url().is_empty());
+            if targets.is_empty() {
+                None
+            } else {
+                Some(ProxyDestinations::Flat(targets))
+            }
+        }
+        Some(ProxyDestinations::ByHost(hosts)) => {
+            let mut normalized: HashMap<String, Vec<ProxyTarget>> = HashMap::new();
+            for (host, mut targets) in hosts {
+                targets.retain(|proxy| !proxy.url().is_empty());
+                if targets.is_empty() {
+                    continue;
+                }
+                normalized.entry(host.to_ascii_lowercase()).or_default().extend(targets);
+            }
+            if normalized.is_empty() {
+                None
+            } else {
+                Some(ProxyDestinations::ByHost(normalized))
+            }
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod proxy_destinations_tests {
+    use super::*;
+
+    fn by_host() -> ProxyDestinations {
+        let mut hosts = HashMap::new();
+        hosts.insert("api.local.gd".to_string(), vec![ProxyTarget::Url("http://127.0.0.1:4000".to_string())]);
+        ProxyDestinations::ByHost(hosts)
+    }
+
+    #[test]
+    fn matches_host_header_carrying_a_port() {
+        let destinations = by_host();
+
+        let targets = destinations.targets_for(Some("api.local.gd:8080"))
+            .expect("Host header with a port suffix should still match the bare hostname key");
+
+        assert_eq!(targets[0].url(), "http://127.0.0.1:4000");
+    }
+
+    #[test]
+    fn matches_host_header_case_insensitively() {
+        let destinations = by_host();
+
+        let targets = destinations.targets_for(Some("API.Local.GD"))
+            .expect("Host header matching should be case-insensitive");
+
+        assert_eq!(targets[0].url(), "http://127.0.0.1:4000");
+    }
+
+    #[test]
+    fn normalize_lowercases_by_host_keys_so_mixed_case_config_matches() {
+        let mut hosts = HashMap::new();
+        hosts.insert("API.Local.GD".to_string(), vec![ProxyTarget::Url("http://127.0.0.1:4000".to_string())]);
+        let destinations = normalize_proxy_destinations(Some(ProxyDestinations::ByHost(hosts)))
+            .expect("non-empty by-host config should survive normalization");
+
+        let targets = destinations.targets_for(Some("api.local.gd"))
+            .expect("a mixed-case config key should match a lowercase Host header after normalization");
+
+        assert_eq!(targets[0].url(), "http://127.0.0.1:4000");
+    }
 }
 
 #[tokio::main]
 async fn main() {
     let opt: Opt  = Opt::from_args();
 
+    if let Some(replay_path) = opt.replay.clone() {
+        TermLogger::init(LevelFilter::Info, LogConfig::default(), TerminalMode::Mixed, simplelog::ColorChoice::Auto)
+            .expect("Failed to initialize logger");
+        let target = opt.replay_target.clone().expect("--replay-target is required when using --replay");
+        run_replay(replay_path, target).await;
+        return;
+    }
+
     let config: Config = {
         let config_content: String = if fs::metadata(&opt.config).is_ok() {
             fs::read_to_string(&opt.config)
@@ -57,19 +1031,12 @@ async fn main() {
         }
 
         if !opt.proxy.is_empty() {
-            config.proxy_destinations = Some(opt.proxy);
+            config.proxy_destinations = Some(ProxyDestinations::Flat(
+                opt.proxy.into_iter().map(ProxyTarget::Url).collect(),
+            ));
         }
 
-        if let Some(mut proxy_destinations_vec) = config.proxy_destinations {
-            proxy_destinations_vec.retain(|proxy| {
-                !proxy.is_empty() && proxy != ""
-            });
-            if proxy_destinations_vec.len() == 0 {
-                config.proxy_destinations = None;
-            } else {
-                config.proxy_destinations = Some(proxy_destinations_vec);
-            }
-        }
+        config.proxy_destinations = normalize_proxy_destinations(config.proxy_destinations);
 
         if let Some(default_resp) = opt.default_response {
             config.default_response = Some(default_resp);
@@ -77,6 +1044,21 @@ async fn main() {
             config.default_response = Some("Default Server Response".to_string());
         }
 
+        if let Some(serve_dir) = opt.serve_dir {
+            config.serve_dir = Some(serve_dir);
+        }
+
+        if opt.proxy_protocol_out {
+            config.proxy_protocol_out = true;
+        }
+        if opt.proxy_protocol_in {
+            config.proxy_protocol_in = true;
+        }
+
+        if let Some(capture) = opt.capture {
+            config.capture = Some(capture);
+        }
+
         config
     };
 
@@ -97,98 +1079,553 @@ async fn main() {
         );
     }
 
-    let default_response = config.default_response.unwrap();
-
-    let make_svc = make_service_fn(move |_conn: &hyper::server::conn::AddrStream| {
-        let client = Client::new();
-        let proxy_destinations  = config.proxy_destinations.clone();
-        let default_response = default_response.clone();
-        async move {
-            Ok::<_, hyper::Error>(service_fn(move |mut req: Request<Body>| {
-                let client = client.clone();
-                let proxy_destinations = proxy_destinations.clone();
-                let default_response = default_response.clone();
-                async move {
-                    let whole_body = to_bytes(req.body_mut()).await?;
-                    let body_str = match String::from_utf8(whole_body.to_vec()) {
-                        Ok(body) => body,
-                        Err(e) => {
-                            eprintln!("Request body is not valid UTF-8: {}", e);
-                            return Ok(Response::new(Body::from("Invalid UTF-8 in request body")));
-                        }
-                    };
-                    
-                    // print the request details
-                    let method = req.method(); // Borrowing reference
-                    let uri = req.uri();       // Borrowing reference
-                    let version = req.version(); // Borrowing reference
-                    let headers = req.headers(); // Borrowing reference
-                    let request_id = Uuid::new_v4();
-                    info!("--- New Request [{}] ---\n\nMethod: {}\nURI: {}\nVersion: {:?}\nHeaders: {:?}\nBody: {}\n", request_id, method, uri, version, headers, body_str);
-                    
-                    if proxy_destinations.is_some() {
-                        let timeout_duration = Duration::from_secs(30);
-                        let mut futures = proxy_destinations.unwrap().into_iter().map(|proxy| {
-                            let client = client.clone();
-                            // Construct the absolute URI for the proxied request
-                            let proxy_uri: hyper::Uri = proxy.parse().expect("Invalid proxy URI");
-                            let absolute_uri = format!("{}://{}{}", proxy_uri.scheme_str().unwrap_or("http"), proxy_uri.authority().unwrap(), uri);
-                            let mut new_req = Request::builder()
-                                .method(req.method())
-                                .uri(absolute_uri)
-                                .version(req.version())
-                                .body(Body::from(body_str.clone()))
-                                .expect("Failed to build request");
-
-                            *new_req.headers_mut() = req.headers().clone();
-
-                            Box::pin(timeout(timeout_duration, client.request(new_req)))
-                        }).collect::<Vec<_>>();
-
-                        while !futures.is_empty() {
-                            let (result, _, remaining_futures) = select_all(futures).await;
-                            futures = remaining_futures;
-
-                            match result {
-                                Ok(Ok(mut response)) => {
-                                    let body_bytes = to_bytes(response.body_mut()).await?;
-                                    let status = response.status();
-                                    let headers = response.headers();
-                                    let body_str = String::from_utf8(body_bytes.to_vec()).expect("Response body is not valid UTF-8");
-
-                                    info!("--- Got Response [{}] ---\n\nStatus: {}\nHeaders: {:?}\nBody: {}\n\n",request_id, status, headers, body_str);
-
-                                    let new_response = Response::builder()
-                                        .status(status)
-                                        .body(Body::from(body_bytes))
-                                        .expect("Failed to build response");
-
-                                    return Ok(new_response);
-                                },
-                                Ok(Err(e)) => {
-                                    warn!("Proxy request failed: {}", e);
-                                },
-                                Err(_) => {
-                                    warn!("Proxy request timed out");
-                                }
-                            }
-                        }
+    let spawned_children = spawn_backends(&config).await;
+
+    let listen_address = config.listen_address.clone().unwrap();
+    let proxy_protocol_in = config.proxy_protocol_in;
+    let shared_config = Arc::new(RwLock::new(config));
+
+    tokio::spawn(watch_config_file(opt.config.clone(), shared_config.clone()));
+
+    if let Some(socket_path) = listen_address.strip_prefix("unix:") {
+        let _ = fs::remove_file(socket_path);
+
+        // Unix domain sockets have no client IP, so X-Forwarded-For falls back to loopback.
+        let client_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+        let make_svc = make_service_fn(move |_conn: &tokio::net::UnixStream| {
+            let client = Client::new();
+            let shared_config = shared_config.clone();
+            async move {
+                Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                    handle_request(req, client.clone(), shared_config.clone(), client_addr)
+                }))
+            }
+        });
+
+        let server = Server::bind_unix(socket_path)
+            .expect("Failed to bind unix socket")
+            .serve(make_svc);
+
+        info!("Server running on unix:{}", socket_path);
+
+        tokio::select! {
+            result = server => {
+                if let Err(e) = result {
+                    eprintln!("Server error: {}", e);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("Shutdown requested, stopping spawned backends");
+            }
+        }
+    } else if proxy_protocol_in {
+        let addr: std::net::SocketAddr = listen_address.parse().expect("Invalid listen address");
+        let listener = TcpListener::bind(addr).await.expect("Failed to bind listen address");
+
+        let make_svc = make_service_fn(move |conn: &ProxyProtocolStream| {
+            let client = Client::new();
+            let shared_config = shared_config.clone();
+            let effective_addr = conn.effective_addr();
+            async move {
+                Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                    let client_addr = *effective_addr.lock().unwrap();
+                    handle_request(req, client.clone(), shared_config.clone(), client_addr)
+                }))
+            }
+        });
+
+        let server = Server::builder(ProxyProtocolIncoming { listener }).serve(make_svc);
+
+        info!("Server running on {} (expecting a PROXY protocol header on each connection)", addr);
+
+        tokio::select! {
+            result = server => {
+                if let Err(e) = result {
+                    eprintln!("Server error: {}", e);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("Shutdown requested, stopping spawned backends");
+            }
+        }
+    } else {
+        let make_svc = make_service_fn(move |conn: &hyper::server::conn::AddrStream| {
+            let client = Client::new();
+            let shared_config = shared_config.clone();
+            let client_addr = conn.remote_addr();
+            async move {
+                Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                    handle_request(req, client.clone(), shared_config.clone(), client_addr)
+                }))
+            }
+        });
+
+        let addr: std::net::SocketAddr = listen_address.parse().expect("Invalid listen address");
+        let server = Server::bind(&addr).serve(make_svc);
+
+        info!("Server running on {}", addr);
+
+        tokio::select! {
+            result = server => {
+                if let Err(e) = result {
+                    eprintln!("Server error: {}", e);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("Shutdown requested, stopping spawned backends");
+            }
+        }
+    }
+
+    for mut child in spawned_children {
+        if let Err(e) = child.kill().await {
+            warn!("Failed to kill spawned backend process: {}", e);
+        }
+    }
+}
+
+/// Handle a single inbound request: log it, route it to a proxy target (TCP
+/// or Unix socket) or the `serve_dir` static root, falling back to the
+/// configured default response.
+async fn handle_request(
+    mut req: Request<Body>,
+    client: Client<HttpConnector>,
+    shared_config: Arc<RwLock<Config>>,
+    client_addr: std::net::SocketAddr,
+) -> Result<Response<Body>, hyper::Error> {
+    let whole_body = to_bytes(req.body_mut()).await?;
+    let body_str = match String::from_utf8(whole_body.to_vec()) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("Request body is not valid UTF-8: {}", e);
+            return Ok(Response::new(Body::from("Invalid UTF-8 in request body")));
+        }
+    };
+
+    // print the request details
+    let method = req.method(); // Borrowing reference
+    let uri = req.uri();       // Borrowing reference
+    let version = req.version(); // Borrowing reference
+    let headers = req.headers(); // Borrowing reference
+    let request_id = Uuid::new_v4();
+    let request_start = std::time::Instant::now();
+    info!("--- New Request [{}] ---\n\nMethod: {}\nURI: {}\nVersion: {:?}\nHeaders: {:?}\nBody: {}\n", request_id, method, uri, version, headers, body_str);
+
+    let request_headers_for_capture = headers_to_map(headers);
+    let request_body_for_capture = body_str.clone();
+
+    let config = shared_config.read().await;
+    let default_response = config.default_response.clone().unwrap();
+    let serve_dir = config.serve_dir.clone();
+    let host_header = req.headers().get("host")
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.to_string());
+    let targets = config.proxy_destinations.as_ref()
+        .and_then(|pd| pd.targets_for(host_header.as_deref()));
+    let proxy_protocol_out = config.proxy_protocol_out;
+    let capture_path = config.capture.clone();
+    drop(config);
+
+    if let Some(targets) = targets {
+        let timeout_duration = Duration::from_secs(30);
+        let mut futures = targets.into_iter().map(|proxy| {
+            let client = client.clone();
+
+            if let Some(socket_path) = proxy.url().strip_prefix("unix:") {
+                let path_and_query = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+                let absolute_uri: hyper::Uri = hyperlocal::Uri::new(socket_path, path_and_query).into();
+                let mut new_req = Request::builder()
+                    .method(req.method())
+                    .uri(absolute_uri)
+                    .version(req.version())
+                    .body(Body::from(body_str.clone()))
+                    .expect("Failed to build request");
+
+                *new_req.headers_mut() = req.headers().clone();
+                strip_hop_by_hop_headers(new_req.headers_mut());
+                apply_forwarded_headers(new_req.headers_mut(), client_addr.ip(), host_header.as_deref());
+
+                let unix_client = Client::unix();
+                return Box::pin(timeout(timeout_duration, unix_client.request(new_req)));
+            }
 
-                        warn!("Returning default response as no valid response received from any proxy");
+            // Construct the absolute URI for the proxied request
+            let proxy_uri: hyper::Uri = proxy.url().parse().expect("Invalid proxy URI");
+            let absolute_uri = format!("{}://{}{}", proxy_uri.scheme_str().unwrap_or("http"), proxy_uri.authority().unwrap(), uri);
+            let mut new_req = Request::builder()
+                .method(req.method())
+                .uri(absolute_uri)
+                .version(req.version())
+                .body(Body::from(body_str.clone()))
+                .expect("Failed to build request");
+
+            *new_req.headers_mut() = req.headers().clone();
+            strip_hop_by_hop_headers(new_req.headers_mut());
+            apply_forwarded_headers(new_req.headers_mut(), client_addr.ip(), host_header.as_deref());
+
+            if proxy_protocol_out {
+                let proxy_protocol_client = Client::builder().build(ProxyProtocolConnector::new(client_addr));
+                return Box::pin(timeout(timeout_duration, proxy_protocol_client.request(new_req)));
+            }
+
+            Box::pin(timeout(timeout_duration, client.request(new_req)))
+        }).collect::<Vec<_>>();
+
+        while !futures.is_empty() {
+            let (result, _, remaining_futures) = select_all(futures).await;
+            futures = remaining_futures;
+
+            match result {
+                Ok(Ok(mut response)) => {
+                    let body_bytes = to_bytes(response.body_mut()).await?;
+                    let status = response.status();
+                    let mut headers = response.headers().clone();
+                    let (body_str, body_is_base64) = encode_capture_body(&body_bytes);
+
+                    if body_is_base64 {
+                        info!("--- Got Response [{}] ---\n\nStatus: {}\nHeaders: {:?}\nBody: <{} bytes, not UTF-8, base64-encoded in capture>\n\n", request_id, status, headers, body_bytes.len());
+                    } else {
+                        info!("--- Got Response [{}] ---\n\nStatus: {}\nHeaders: {:?}\nBody: {}\n\n",request_id, status, headers, body_str);
                     }
-                    
-                    Ok::<_, hyper::Error>(Response::new(Body::from(default_response)))
+
+                    if let Some(path) = &capture_path {
+                        let record = CaptureRecord {
+                            request_id: request_id.to_string(),
+                            timestamp_ms: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_millis(),
+                            method: method.to_string(),
+                            uri: uri.to_string(),
+                            request_headers: request_headers_for_capture.clone(),
+                            request_body: request_body_for_capture.clone(),
+                            status: status.as_u16(),
+                            response_headers: headers_to_map(&headers),
+                            response_body: body_str.clone(),
+                            response_body_base64: body_is_base64,
+                            latency_ms: request_start.elapsed().as_millis(),
+                        };
+                        write_capture_record(path, &record);
+                    }
+
+                    strip_hop_by_hop_headers(&mut headers);
+
+                    let mut new_response = Response::builder()
+                        .status(status)
+                        .body(Body::from(body_bytes))
+                        .expect("Failed to build response");
+                    *new_response.headers_mut() = headers;
+
+                    return Ok(new_response);
+                },
+                Ok(Err(e)) => {
+                    warn!("Proxy request failed: {}", e);
+                },
+                Err(_) => {
+                    warn!("Proxy request timed out");
                 }
+            }
+        }
+
+        warn!("No valid response received from any proxy, falling back to serve_dir or the default response");
+    }
+
+    if let Some(serve_dir) = serve_dir {
+        return Ok(serve_static_file(&serve_dir, uri.path()));
+    }
+
+    Ok::<_, hyper::Error>(Response::new(Body::from(default_response)))
+}
+
+#[cfg(test)]
+mod handle_request_unix_target_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn proxies_to_a_unix_socket_target() {
+        let socket_path = std::env::temp_dir().join(format!("dev-server-test-proxy-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&socket_path);
+
+        let make_svc = make_service_fn(|_conn: &tokio::net::UnixStream| async {
+            Ok::<_, hyper::Error>(service_fn(|_req: Request<Body>| async {
+                Ok::<_, hyper::Error>(Response::new(Body::from("hello from unix backend")))
             }))
+        });
+        let server = Server::bind_unix(&socket_path).unwrap().serve(make_svc);
+        tokio::spawn(server);
+        // Give the backend listener a moment to start accepting before the
+        // proxied request races it.
+        sleep(Duration::from_millis(50)).await;
+
+        let config = Config {
+            listen_address: Some("unix:/tmp/unused.sock".to_string()),
+            proxy_destinations: Some(ProxyDestinations::Flat(vec![
+                ProxyTarget::Url(format!("unix:{}", socket_path.display())),
+            ])),
+            default_response: Some("default".to_string()),
+            serve_dir: None,
+            proxy_protocol_out: false,
+            proxy_protocol_in: false,
+            capture: None,
+        };
+        let shared_config = Arc::new(RwLock::new(config));
+
+        let req = Request::builder().method("GET").uri("/").body(Body::empty()).unwrap();
+        let client_addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        let response = handle_request(req, Client::new(), shared_config, client_addr).await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        let body = to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"hello from unix backend");
+
+        let _ = fs::remove_file(&socket_path);
+    }
+}
+
+/// Spawn and supervise a backend process for every configured proxy target
+/// that has a `spawn` section, then wait for all of them to start accepting
+/// connections concurrently before returning (so N spawned backends take as
+/// long as the slowest one to become ready, not the sum of all of them). The
+/// returned children are kept alive for the lifetime of the server and
+/// killed on shutdown.
+async fn spawn_backends(config: &Config) -> Vec<Child> {
+    let targets = match &config.proxy_destinations {
+        Some(destinations) => destinations.all_targets(),
+        None => Vec::new(),
+    };
+
+    let mut children = Vec::new();
+    let mut ready_urls = Vec::new();
+    for target in targets {
+        let spawn_config = match target.spawn() {
+            Some(spawn_config) => spawn_config,
+            None => continue,
+        };
+
+        info!("Spawning backend `{}` for proxy target {}", spawn_config.command, target.url());
+        let mut command = TokioCommand::new(&spawn_config.command);
+        command.args(&spawn_config.args);
+        for (key, value) in &spawn_config.envs {
+            command.env(key, value);
         }
-    });
 
-    let addr: std::net::SocketAddr = config.listen_address.as_ref().unwrap().parse().expect("Invalid listen address");
-    let server = Server::bind(&addr).serve(make_svc);
+        let child = command.spawn().expect("Failed to spawn backend process");
+        children.push(child);
+        ready_urls.push(target.url().to_string());
+    }
+
+    join_all(ready_urls.iter().map(|url| wait_for_target_ready(url))).await;
+
+    children
+}
 
-    info!("Server running on {}", addr);
+/// Poll a proxy target until it accepts connections, or give up after
+/// `SPAWN_READY_RETRIES` attempts and let the proxy loop's own timeout
+/// handle a backend that is still not ready. Handles both TCP targets and
+/// `unix:` socket targets, the same way `handle_request` distinguishes them.
+async fn wait_for_target_ready(url: &str) {
+    if let Some(socket_path) = url.strip_prefix("unix:") {
+        for attempt in 1..=SPAWN_READY_RETRIES {
+            if UnixStream::connect(socket_path).await.is_ok() {
+                info!("Backend {} is accepting connections", socket_path);
+                return;
+            }
+            warn!("Waiting for backend {} to accept connections (attempt {}/{})", socket_path, attempt, SPAWN_READY_RETRIES);
+            sleep(SPAWN_READY_RETRY_DELAY).await;
+        }
+
+        warn!("Backend {} did not become ready in time, proxying to it anyway", socket_path);
+        return;
+    }
+
+    let uri: hyper::Uri = url.parse().expect("Invalid proxy URI");
+    let authority = uri.authority().expect("Proxy target must include host:port").to_string();
+
+    for attempt in 1..=SPAWN_READY_RETRIES {
+        if TcpStream::connect(&authority).await.is_ok() {
+            info!("Backend {} is accepting connections", authority);
+            return;
+        }
+        warn!("Waiting for backend {} to accept connections (attempt {}/{})", authority, attempt, SPAWN_READY_RETRIES);
+        sleep(SPAWN_READY_RETRY_DELAY).await;
+    }
+
+    warn!("Backend {} did not become ready in time, proxying to it anyway", authority);
+}
+
+#[cfg(test)]
+mod wait_for_target_ready_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn returns_as_soon_as_a_tcp_target_accepts_connections() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = listener.accept().await;
+        });
+
+        timeout(Duration::from_secs(1), wait_for_target_ready(&format!("http://{}", addr))).await
+            .expect("wait_for_target_ready must return once the TCP target starts accepting connections");
+    }
+
+    #[tokio::test]
+    async fn returns_as_soon_as_a_unix_target_accepts_connections() {
+        let socket_path = std::env::temp_dir().join(format!("dev-server-test-ready-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&socket_path);
+        let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+        let accept_path = socket_path.clone();
+        tokio::spawn(async move {
+            let _ = listener.accept().await;
+            let _ = fs::remove_file(&accept_path);
+        });
+
+        timeout(Duration::from_secs(1), wait_for_target_ready(&format!("unix:{}", socket_path.display()))).await
+            .expect("wait_for_target_ready must return once the unix target starts accepting connections");
+    }
+}
+
+/// Poll `path`'s mtime on an interval and, whenever it changes, re-read and
+/// re-parse it, atomically swapping the live `proxy_destinations`/
+/// `default_response` used by the service. A file that fails to parse is
+/// logged and ignored, keeping the previously loaded configuration live.
+///
+/// Every field a CLI flag can also set falls back to the value already in
+/// `shared_config` when the reloaded file doesn't mention it, so a reload
+/// never wipes a CLI-only override. One consequence: once a field has been
+/// set by either the CLI or an earlier config file, a later reload can only
+/// replace it, never clear it back to `None` — removing a `proxy_destinations`
+/// or `serve_dir` entry from the file on disk has no effect once it's live.
+/// That's intentional (this is a dev tool watching for iteration on values,
+/// not a general-purpose config sync); restart the process to actually clear one.
+async fn watch_config_file(path: String, shared_config: Arc<RwLock<Config>>) {
+    let mut last_modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+    let mut interval = tokio::time::interval(CONFIG_RELOAD_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let modified = match fs::metadata(&path).and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(_) => continue,
+        };
+        if Some(modified) == last_modified {
+            continue;
+        }
+        last_modified = Some(modified);
+
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) => {
+                warn!("Failed to read configuration file {} after change: {}", path, e);
+                continue;
+            }
+        };
+
+        let new_config: Config = match serde_yaml::from_str(&raw) {
+            Ok(new_config) => new_config,
+            Err(e) => {
+                warn!("Failed to parse configuration file {} after change, keeping previous config: {}", path, e);
+                continue;
+            }
+        };
+
+        let mut config = shared_config.write().await;
+        // proxy_destinations can also be set via a CLI flag (--proxy); preserve it
+        // the same way as the fields below when the freshly reloaded file doesn't
+        // mention it, instead of wiping a CLI-only override on every reload.
+        config.proxy_destinations = normalize_proxy_destinations(new_config.proxy_destinations)
+            .or(config.proxy_destinations.take());
+        config.default_response = new_config.default_response.or(config.default_response.take());
+        // serve_dir, proxy_protocol_out and capture can all be set via a CLI flag
+        // instead of the config file; preserve whatever is already in the shared
+        // config (CLI override or previous file value) when the freshly reloaded
+        // file doesn't mention them, the same way default_response is handled.
+        config.serve_dir = new_config.serve_dir.or(config.serve_dir.take());
+        // proxy_protocol_in selects how the listener itself is bound at startup and
+        // can't be changed without rebinding, so it is intentionally left alone here.
+        config.proxy_protocol_out = new_config.proxy_protocol_out || config.proxy_protocol_out;
+        config.capture = new_config.capture.or(config.capture.take());
+        drop(config);
+
+        info!("Reloaded configuration from {}", path);
+    }
+}
+
+#[cfg(test)]
+mod watch_config_file_tests {
+    use super::*;
+
+    fn temp_config_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("dev-server-test-watch-{}-{}.yml", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    /// Write `contents`, wait for `watch_config_file` to notice and reload it, and
+    /// return once `shared_config.default_response` matches `expect_default_response`
+    /// (or panic after a handful of reload intervals).
+    async fn write_and_wait_for_reload(path: &std::path::Path, shared_config: &Arc<RwLock<Config>>, contents: &str, expect_default_response: &str) {
+        fs::write(path, contents).unwrap();
+
+        for _ in 0..5 {
+            sleep(CONFIG_RELOAD_INTERVAL).await;
+            if shared_config.read().await.default_response.as_deref() == Some(expect_default_response) {
+                return;
+            }
+        }
+        panic!("watch_config_file did not reload {} within the expected number of intervals", path.display());
+    }
+
+    #[tokio::test]
+    async fn reload_overrides_a_field_present_in_the_file() {
+        let path = temp_config_path("override");
+        fs::write(&path, "default_response: initial\n").unwrap();
+
+        let config = Config {
+            listen_address: Some("127.0.0.1:0".to_string()),
+            proxy_destinations: None,
+            default_response: Some("initial".to_string()),
+            serve_dir: None,
+            proxy_protocol_out: false,
+            proxy_protocol_in: false,
+            capture: None,
+        };
+        let shared_config = Arc::new(RwLock::new(config));
+        tokio::spawn(watch_config_file(path.to_str().unwrap().to_string(), shared_config.clone()));
+
+        sleep(Duration::from_millis(100)).await;
+        write_and_wait_for_reload(&path, &shared_config, "default_response: from-file\n", "from-file").await;
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn reload_preserves_a_cli_set_field_absent_from_the_file() {
+        let path = temp_config_path("preserve");
+        fs::write(&path, "default_response: initial\n").unwrap();
+
+        let config = Config {
+            listen_address: Some("127.0.0.1:0".to_string()),
+            proxy_destinations: Some(ProxyDestinations::Flat(vec![ProxyTarget::Url("http://cli-override:1234".to_string())])),
+            default_response: Some("initial".to_string()),
+            serve_dir: None,
+            proxy_protocol_out: false,
+            proxy_protocol_in: false,
+            capture: None,
+        };
+        let shared_config = Arc::new(RwLock::new(config));
+        tokio::spawn(watch_config_file(path.to_str().unwrap().to_string(), shared_config.clone()));
+
+        sleep(Duration::from_millis(100)).await;
+        // The reloaded file never mentions proxy_destinations; the CLI-set value must survive.
+        write_and_wait_for_reload(&path, &shared_config, "default_response: from-file\n", "from-file").await;
+
+        let config = shared_config.read().await;
+        match &config.proxy_destinations {
+            Some(ProxyDestinations::Flat(targets)) => assert_eq!(targets[0].url(), "http://cli-override:1234"),
+            other => panic!("expected the CLI-set proxy_destinations to survive a reload that doesn't mention it, got {:?}", other),
+        }
+        drop(config);
 
-    if let Err(e) = server.await {
-        eprintln!("Server error: {}", e);
+        let _ = fs::remove_file(&path);
     }
 }